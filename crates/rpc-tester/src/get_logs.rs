@@ -1,17 +1,94 @@
 //! Helper for `eth_getLogs` with automatic retry on "max results exceeded" errors.
 
+use alloy_json_rpc::RpcError;
 use alloy_primitives::BlockNumber;
 use alloy_provider::{network::AnyNetwork, Provider};
-use alloy_rpc_types::{BlockNumberOrTag, Filter, Log};
-use futures::FutureExt;
+use alloy_rpc_types::{BlockNumberOrTag, Filter, FilterBlockOption, Log};
+use alloy_transport::TransportErrorKind;
+use futures::{
+    stream::{self, StreamExt, TryStreamExt},
+    FutureExt,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 
 /// The result type returned by `get_logs`.
-pub type GetLogsResult<T> =
-    Result<T, alloy_json_rpc::RpcError<alloy_transport::TransportErrorKind>>;
+pub type GetLogsResult<T> = Result<T, ProviderError>;
+
+/// The error type returned by the underlying provider.
+type ProviderError = RpcError<TransportErrorKind>;
 
 /// Maximum recursion depth to prevent infinite loops.
 const MAX_RECURSION_DEPTH: u32 = 10;
 
+/// Default number of chunk requests that may be in flight at once during pagination.
+///
+/// Bounded concurrency gives rate-limited endpoints the back-pressure they need; public RPCs
+/// generally want this dialed down, local nodes can push it up.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// A concrete, tag-free block range, already pinned to the same height on every provider under
+/// test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedBlockRange {
+    pub from: BlockNumber,
+    pub to: BlockNumber,
+}
+
+/// Resolves `latest`/`pending`/`safe`/`finalized` block tags in `filter` to the same concrete
+/// block height on both providers under test.
+///
+/// `eth_getLogs` (and other `latest`-dependent methods) would otherwise let rpc1 and rpc2 resolve
+/// "latest" to different heights, producing spurious diffs that have nothing to do with client
+/// behavior. This queries `eth_blockNumber` on both providers and pins to `min(head1, head2) -
+/// lag`, mirroring reth's practice of snapshotting `ChainInfo` to a fixed block before serving
+/// `eth_getLogs` on the head. Call this once per test run so every method sees the same
+/// deterministic, race-free range.
+///
+/// `Earliest` resolves to block `0`, not the pinned height; [`apply_resolved_range`] leaves
+/// `AtBlockHash` filters untouched entirely, since a block hash doesn't have a "latest" to race.
+pub async fn resolve_block_range<P1, P2>(
+    rpc1: &P1,
+    rpc2: &P2,
+    filter: &Filter,
+    lag: u64,
+) -> GetLogsResult<ResolvedBlockRange>
+where
+    P1: Provider<AnyNetwork>,
+    P2: Provider<AnyNetwork>,
+{
+    let (head1, head2) = tokio::try_join!(rpc1.get_block_number(), rpc2.get_block_number())?;
+    let pinned = head1.min(head2).saturating_sub(lag);
+
+    let from = resolve_tag(filter.block_option.get_from_block().copied(), pinned);
+    let to = resolve_tag(filter.block_option.get_to_block().copied(), pinned);
+
+    Ok(ResolvedBlockRange { from, to })
+}
+
+/// Resolves a single from/to block tag to a concrete number. An explicit number or `earliest` is
+/// already concrete; everything else (`latest`/`pending`/`safe`/`finalized`/unset) pins to
+/// `pinned`.
+fn resolve_tag(tag: Option<BlockNumberOrTag>, pinned: BlockNumber) -> BlockNumber {
+    match tag {
+        Some(BlockNumberOrTag::Number(n)) => n,
+        Some(BlockNumberOrTag::Earliest) => 0,
+        _ => pinned,
+    }
+}
+
+/// Rewrites a filter's block range to the given concrete, already-resolved range.
+///
+/// `AtBlockHash` filters are left untouched: a block hash already pins an exact block, so there's
+/// no tag to race and overwriting it would silently discard the hash the caller asked for.
+fn apply_resolved_range(filter: Filter, range: ResolvedBlockRange) -> Filter {
+    match filter.block_option {
+        FilterBlockOption::AtBlockHash(_) => filter,
+        FilterBlockOption::Range { .. } => filter.from_block(range.from).to_block(range.to),
+    }
+}
+
 /// Fetches logs with automatic pagination when the RPC returns a "max results exceeded" error.
 ///
 /// Some RPC providers limit the number of logs returned in a single request. When exceeded,
@@ -20,64 +97,129 @@ const MAX_RECURSION_DEPTH: u32 = 10;
 ///
 /// This function parses such errors and paginates through the full block range using the
 /// suggested chunk size, collecting all results.
+///
+/// `range` must already be resolved to concrete block numbers (see [`resolve_block_range`]) so
+/// tag-based filters (`latest`, `pending`, ...) can't drift between rpc1 and rpc2 mid-run.
+///
+/// Up to `max_concurrent_requests` provider calls are in flight at once, across the *entire*
+/// recursion tree, not just siblings at one level; pass [`DEFAULT_MAX_CONCURRENT_REQUESTS`] if you
+/// don't need to tune it.
 pub async fn get_logs_with_retry<P: Provider<AnyNetwork>>(
     provider: &P,
     filter: &Filter,
+    range: ResolvedBlockRange,
+    max_concurrent_requests: usize,
 ) -> GetLogsResult<Vec<Log>> {
-    get_logs_paginated(provider, filter.clone(), 0).await
+    let filter = apply_resolved_range(filter.clone(), range);
+    let semaphore = Arc::new(Semaphore::new(max_concurrent_requests.max(1)));
+    get_logs_paginated(provider, filter, 0, semaphore).await
 }
 
-/// Recursively fetches logs, splitting the range when "max results exceeded" is returned.
+/// Recursively fetches logs, splitting the range when a "too many results" error is returned.
+///
+/// Every actual `get_logs` call, at any depth, acquires a permit from the shared `semaphore`
+/// before running, so `max_concurrent_requests` bounds the number of in-flight provider calls
+/// globally. Without this, each of the `N` concurrently-dispatched chunk/half futures could itself
+/// recurse and open its own locally-bounded batch of children, multiplying the real concurrency to
+/// `N^depth`.
 fn get_logs_paginated<'a, P: Provider<AnyNetwork>>(
     provider: &'a P,
     filter: Filter,
     depth: u32,
+    semaphore: Arc<Semaphore>,
 ) -> futures::future::BoxFuture<'a, GetLogsResult<Vec<Log>>> {
     async move {
         if depth > MAX_RECURSION_DEPTH {
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
             return provider.get_logs(&filter).await;
         }
 
-        match provider.get_logs(&filter).await {
+        let attempt = {
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+            provider.get_logs(&filter).await
+        };
+
+        match attempt {
             Ok(logs) => Ok(logs),
             Err(e) => {
-                let Some((suggested_from, suggested_to)) = parse_max_results_error(&e) else {
+                let Some(hint) = error_parsers().iter().find_map(|parse| parse(&e)) else {
                     return Err(e);
                 };
 
-                let Some(chunk_size) =
-                    suggested_to.checked_sub(suggested_from).and_then(|d| d.checked_add(1))
-                else {
-                    return Err(e);
+                let (original_from, original_to) = match extract_block_range(&filter) {
+                    Some(range) => range,
+                    None => match hint {
+                        RangeHint::Range(from, to) => (from, to),
+                        RangeHint::MaxBlockCount(_) | RangeHint::Unbounded => return Err(e),
+                    },
                 };
 
-                let (original_from, original_to) =
-                    extract_block_range(&filter).unwrap_or((suggested_from, suggested_to));
-
                 if original_from > original_to {
                     return Err(e);
                 }
 
+                let chunk_size = match hint {
+                    RangeHint::Range(from, to) => {
+                        to.checked_sub(from).and_then(|d| d.checked_add(1))
+                    }
+                    RangeHint::MaxBlockCount(n) => Some(n),
+                    RangeHint::Unbounded => None,
+                };
+
+                // No usable chunk size: the provider told us the range was too wide but not by
+                // how much, so fall back to binary splitting until a half succeeds or we're down
+                // to a single block that still fails. Both halves share the same `semaphore` as
+                // every other call in the tree, so `max_concurrent_requests` is honored globally
+                // here too, instead of just bounding these two siblings.
+                let Some(chunk_size) = chunk_size else {
+                    if original_from == original_to {
+                        return Err(e);
+                    }
+                    let mid = original_from + (original_to - original_from) / 2;
+                    let halves = [(original_from, mid), (mid + 1, original_to)];
+
+                    let halved_logs: Vec<Vec<Log>> = stream::iter(halves)
+                        .map(|(from, to)| {
+                            let half_filter = filter.clone().from_block(from).to_block(to);
+                            get_logs_paginated(provider, half_filter, depth + 1, semaphore.clone())
+                        })
+                        .buffered(2)
+                        .try_collect()
+                        .await?;
+
+                    return Ok(halved_logs.into_iter().flatten().collect());
+                };
+
                 let original_len = original_to - original_from + 1;
                 if chunk_size >= original_len && depth > 0 {
                     return Err(e);
                 }
 
-                let mut all_logs = Vec::new();
+                let mut chunk_ranges = Vec::new();
                 let mut current_from = original_from;
-
                 while current_from <= original_to {
                     let current_to = current_from.saturating_add(chunk_size - 1).min(original_to);
-                    let chunk_filter = filter.clone().from_block(current_from).to_block(current_to);
-                    let chunk_logs = get_logs_paginated(provider, chunk_filter, depth + 1).await?;
-                    all_logs.extend(chunk_logs);
+                    chunk_ranges.push((current_from, current_to));
                     current_from = match current_to.checked_add(1) {
                         Some(v) => v,
                         None => break,
                     };
                 }
 
-                Ok(all_logs)
+                // The `buffered` bound here just caps how many of this level's own chunk futures
+                // are polled at once; the real cross-recursion limit is the shared `semaphore`
+                // each one acquires before actually calling the provider.
+                let chunk_count = chunk_ranges.len().max(1);
+                let chunked_logs: Vec<Vec<Log>> = stream::iter(chunk_ranges)
+                    .map(|(from, to)| {
+                        let chunk_filter = filter.clone().from_block(from).to_block(to);
+                        get_logs_paginated(provider, chunk_filter, depth + 1, semaphore.clone())
+                    })
+                    .buffered(chunk_count)
+                    .try_collect()
+                    .await?;
+
+                Ok(chunked_logs.into_iter().flatten().collect())
             }
         }
     }
@@ -97,17 +239,48 @@ fn extract_block_range(filter: &Filter) -> Option<(BlockNumber, BlockNumber)> {
     Some((from, to))
 }
 
-/// Parses an error to extract the suggested block range from "max results exceeded" errors.
+/// A hint, extracted from a provider's "too many results" error, about how to shrink the query
+/// range for the retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeHint {
+    /// The provider suggested an exact `[from, to]` range to retry with.
+    Range(BlockNumber, BlockNumber),
+    /// The provider told us the max number of blocks per request, but not a range.
+    MaxBlockCount(BlockNumber),
+    /// The provider rejected the range but gave no usable size hint; caller should fall back to
+    /// binary splitting.
+    Unbounded,
+}
+
+/// A parser that tries to extract a [`RangeHint`] from a provider error.
+type ErrorParser = Box<dyn Fn(&ProviderError) -> Option<RangeHint>>;
+
+/// The registry of known provider error formats, tried in order until one matches.
 ///
-/// Expected format: "query exceeds max results N, retry with the range FROM-TO"
-fn parse_max_results_error<E: std::fmt::Display>(error: &E) -> Option<(BlockNumber, BlockNumber)> {
-    let msg = error.to_string();
+/// Providers disagree wildly on how they report "too many results": reth/Erigon suggest an exact
+/// range, Infura just names a result-count limit, Alchemy names a block-count limit, and others
+/// put a structured `limit`/`from`/`to` object in the JSON-RPC error `data` field.
+fn error_parsers() -> Vec<ErrorParser> {
+    vec![
+        Box::new(parse_reth_max_results_error),
+        Box::new(parse_alchemy_block_range_error),
+        Box::new(parse_infura_max_results_error),
+        Box::new(parse_structured_limit_error),
+    ]
+}
+
+/// Parses reth/Erigon-style errors: "query exceeds max results N, retry with the range FROM-TO".
+fn parse_reth_max_results_error(error: &ProviderError) -> Option<RangeHint> {
+    parse_suggested_range(&error.to_string())
+}
 
+/// Parses "range FROM-TO" out of a message like
+/// "query exceeds max results 20000, retry with the range 24383075-24383096".
+fn parse_suggested_range(msg: &str) -> Option<RangeHint> {
     if !msg.contains("max results") {
         return None;
     }
 
-    // Look for pattern like "range 24383075-24383096"
     let range_prefix = "range ";
     let range_start = msg.find(range_prefix)?;
     let range_part = &msg[range_start + range_prefix.len()..];
@@ -121,7 +294,56 @@ fn parse_max_results_error<E: std::fmt::Display>(error: &E) -> Option<(BlockNumb
     let from: BlockNumber = parts.next()?.parse().ok()?;
     let to: BlockNumber = parts.next()?.parse().ok()?;
 
-    Some((from, to))
+    Some(RangeHint::Range(from, to))
+}
+
+/// Parses Infura-style errors: "query returned more than 10000 results". Infura reports a result
+/// count, not a block range or count, so there's no usable chunk size here.
+fn parse_infura_max_results_error(error: &ProviderError) -> Option<RangeHint> {
+    let msg = error.to_string();
+    (msg.contains("returned more than") && msg.contains("results")).then_some(RangeHint::Unbounded)
+}
+
+/// Parses Alchemy-style errors: "Log response size exceeded. You can make eth_getLogs requests
+/// with up to a 2000 block range ... [0x..., 0x...]".
+fn parse_alchemy_block_range_error(error: &ProviderError) -> Option<RangeHint> {
+    let msg = error.to_string();
+    let prefix = "up to a ";
+    let start = msg.find(prefix)?;
+    let rest = &msg[start + prefix.len()..];
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let count: BlockNumber = rest[..end].parse().ok()?;
+    Some(RangeHint::MaxBlockCount(count))
+}
+
+/// Parses providers that return a structured `-32005`-style error with `limit`/`from`/`to` in
+/// the JSON-RPC error's `data` field. Reading the structured field instead of the display string
+/// means numeric limits survive whatever formatting the transport layer applies to the message.
+fn parse_structured_limit_error(error: &ProviderError) -> Option<RangeHint> {
+    let RpcError::ErrorResp(payload) = error else { return None };
+    let data = payload.data.as_ref()?;
+    let value: Value = serde_json::from_str(data.get()).ok()?;
+
+    if let (Some(from), Some(to)) = (value.get("from"), value.get("to")) {
+        return Some(RangeHint::Range(
+            parse_hex_or_dec_block_number(from)?,
+            parse_hex_or_dec_block_number(to)?,
+        ));
+    }
+
+    parse_hex_or_dec_block_number(value.get("limit")?).map(RangeHint::MaxBlockCount)
+}
+
+/// Parses a JSON number that may be a plain integer or a `0x`-prefixed hex string.
+fn parse_hex_or_dec_block_number(value: &Value) -> Option<BlockNumber> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => BlockNumber::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -131,22 +353,56 @@ mod tests {
     #[test]
     fn test_parse_max_results_error_message() {
         let error_msg = "query exceeds max results 20000, retry with the range 24383075-24383096";
-        let result = parse_max_results_error(&error_msg);
-        assert_eq!(result, Some((24383075, 24383096)));
+        let result = parse_suggested_range(error_msg);
+        assert_eq!(result, Some(RangeHint::Range(24383075, 24383096)));
     }
 
     #[test]
     fn test_parse_non_matching_error() {
         let error_msg = "some other error";
-        let result = parse_max_results_error(&error_msg);
+        let result = parse_suggested_range(error_msg);
         assert_eq!(result, None);
     }
 
     #[test]
     fn test_parse_with_trailing_text() {
         let error_msg = "query exceeds max results 20000, retry with the range 100-200, extra info";
-        let result = parse_max_results_error(&error_msg);
-        assert_eq!(result, Some((100, 200)));
+        let result = parse_suggested_range(error_msg);
+        assert_eq!(result, Some(RangeHint::Range(100, 200)));
+    }
+
+    #[test]
+    fn test_parse_alchemy_block_range_error() {
+        let payload = alloy_json_rpc::ErrorPayload {
+            code: -32602,
+            message: "Log response size exceeded. You can make eth_getLogs requests with up to a \
+                      2000 block range ... [0x..., 0x...]"
+                .into(),
+            data: None,
+        };
+        let error = ProviderError::ErrorResp(payload);
+        assert_eq!(parse_alchemy_block_range_error(&error), Some(RangeHint::MaxBlockCount(2000)));
+    }
+
+    #[test]
+    fn test_parse_hex_or_dec_block_number() {
+        assert_eq!(parse_hex_or_dec_block_number(&Value::String("0x2710".to_string())), Some(10000));
+        assert_eq!(parse_hex_or_dec_block_number(&Value::String("10000".to_string())), Some(10000));
+        assert_eq!(parse_hex_or_dec_block_number(&Value::Number(10000.into())), Some(10000));
+    }
+
+    #[test]
+    fn test_parse_structured_limit_error_range() {
+        let payload = alloy_json_rpc::ErrorPayload {
+            code: -32005,
+            message: "query exceeds limit".into(),
+            data: Some(
+                serde_json::value::to_raw_value(&serde_json::json!({"from": "0x0", "to": "0x3e8"}))
+                    .unwrap(),
+            ),
+        };
+        let error = ProviderError::ErrorResp(payload);
+        assert_eq!(parse_structured_limit_error(&error), Some(RangeHint::Range(0, 1000)));
     }
 
     #[test]
@@ -160,4 +416,27 @@ mod tests {
         let filter = Filter::new();
         assert_eq!(extract_block_range(&filter), None);
     }
+
+    #[test]
+    fn test_apply_resolved_range_overwrites_tags() {
+        let filter = Filter::new().from_block(BlockNumberOrTag::Latest);
+        let range = ResolvedBlockRange { from: 100, to: 200 };
+        assert_eq!(apply_resolved_range(filter, range), Filter::new().from_block(100u64).to_block(200u64));
+    }
+
+    #[test]
+    fn test_apply_resolved_range_leaves_block_hash_filters_untouched() {
+        let filter = Filter::new().at_block_hash(alloy_primitives::B256::ZERO);
+        let range = ResolvedBlockRange { from: 100, to: 200 };
+        assert_eq!(apply_resolved_range(filter.clone(), range), filter);
+    }
+
+    #[test]
+    fn test_resolve_tag() {
+        assert_eq!(resolve_tag(Some(BlockNumberOrTag::Number(42)), 100), 42);
+        assert_eq!(resolve_tag(Some(BlockNumberOrTag::Earliest), 100), 0);
+        assert_eq!(resolve_tag(Some(BlockNumberOrTag::Latest), 100), 100);
+        assert_eq!(resolve_tag(Some(BlockNumberOrTag::Pending), 100), 100);
+        assert_eq!(resolve_tag(None, 100), 100);
+    }
 }
@@ -0,0 +1,185 @@
+//! Configurable, path-aware ignore rules for fields excluded from rpc1/rpc2 comparison.
+//!
+//! Replaces a single hardcoded field list with a rule set that can be loaded from a config file,
+//! scoped to a specific rpc method, and targeted at a JSON path rather than a bare key name.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A single ignore rule: a JSON path to strip, optionally scoped to one rpc method.
+///
+/// `path` uses a small subset of JSONPath: dotted field names and a `[*]` suffix meaning "every
+/// element of the array at this point", e.g. `result.logs[*].removed`. A bare key with no `.` or
+/// `[*]` (e.g. `error`) matches that key at any depth, mirroring the old blunt behavior.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct IgnoreRule {
+    /// The rpc method this rule applies to, e.g. `"eth_getTransactionReceipt"`. `None` applies
+    /// the rule to every method.
+    #[serde(default)]
+    pub method: Option<String>,
+    /// The JSON path to strip.
+    pub path: String,
+}
+
+/// The raw, on-disk shape of an ignore rule set, before paths are parsed.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RawIgnoreRules {
+    #[serde(default)]
+    rules: Vec<IgnoreRule>,
+}
+
+/// One rule with its path pre-parsed, so [`IgnoreRules::apply`] doesn't re-parse the same path
+/// string on every call.
+#[derive(Debug, Clone)]
+struct ParsedRule {
+    method: Option<String>,
+    segments: Vec<PathSegment>,
+}
+
+/// The active set of ignore rules, typically loaded once from a config file at startup.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IgnoreRules {
+    rules: Vec<ParsedRule>,
+}
+
+impl IgnoreRules {
+    fn from_raw(raw: RawIgnoreRules) -> Self {
+        Self {
+            rules: raw
+                .rules
+                .into_iter()
+                .map(|rule| ParsedRule { method: rule.method, segments: parse_path(&rule.path) })
+                .collect(),
+        }
+    }
+
+    /// The rule set used when no user configuration is supplied: strips the client-specific
+    /// `error` extension (e.g. Nethermind's field on reverted transaction receipts) at any depth,
+    /// for every method.
+    pub fn default_rules() -> Self {
+        Self::from_raw(RawIgnoreRules {
+            rules: vec![IgnoreRule { method: None, path: "error".to_string() }],
+        })
+    }
+
+    /// Loads ignore rules from a JSON config file, e.g. `{"rules": [{"method":
+    /// "eth_getTransactionReceipt", "path": "error"}, {"path": "result.logs[*].removed"}]}`.
+    pub fn from_json_file(path: impl AsRef<std::path::Path>) -> eyre::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let raw: RawIgnoreRules = serde_json::from_str(&contents)?;
+        Ok(Self::from_raw(raw))
+    }
+
+    /// Strips every rule scoped to `method` (or unscoped) from `value`.
+    pub fn apply(&self, method: &str, value: Value) -> Value {
+        self.rules
+            .iter()
+            .filter(|rule| rule.method.as_deref().is_none_or(|m| m == method))
+            .fold(value, |value, rule| strip_path(value, &rule.segments))
+    }
+}
+
+/// A single parsed path segment.
+#[derive(Debug, Clone)]
+enum PathSegment {
+    /// Match this field name.
+    Field(String),
+    /// Match every element of the array at this point in the path.
+    Wildcard,
+}
+
+/// Parses a dotted path like `result.logs[*].removed` into segments.
+fn parse_path(path: &str) -> Vec<PathSegment> {
+    path.split('.')
+        .flat_map(|part| match part.strip_suffix("[*]") {
+            Some(name) => vec![PathSegment::Field(name.to_string()), PathSegment::Wildcard],
+            None => vec![PathSegment::Field(part.to_string())],
+        })
+        .collect()
+}
+
+/// Strips `segments` from `value`. A bare single-field path matches at any depth; anything longer
+/// is navigated from the root.
+fn strip_path(value: Value, segments: &[PathSegment]) -> Value {
+    match segments {
+        [PathSegment::Field(name)] => strip_key_anywhere(value, name),
+        _ => strip_anchored(value, segments),
+    }
+}
+
+/// Recursively removes `key` from every object in the tree, regardless of depth.
+fn strip_key_anywhere(value: Value, key: &str) -> Value {
+    match value {
+        Value::Object(mut map) => {
+            map.remove(key);
+            Value::Object(map.into_iter().map(|(k, v)| (k, strip_key_anywhere(v, key))).collect())
+        }
+        Value::Array(arr) => Value::Array(arr.into_iter().map(|v| strip_key_anywhere(v, key)).collect()),
+        other => other,
+    }
+}
+
+/// Removes the field named by the last segment, navigating there exactly via the earlier
+/// segments (following `Wildcard` into every array element along the way).
+fn strip_anchored(value: Value, segments: &[PathSegment]) -> Value {
+    match segments.split_first() {
+        None => value,
+        Some((PathSegment::Wildcard, rest)) => match value {
+            Value::Array(arr) => {
+                Value::Array(arr.into_iter().map(|v| strip_anchored(v, rest)).collect())
+            }
+            other => other,
+        },
+        Some((PathSegment::Field(name), rest)) => match value {
+            Value::Object(mut map) => {
+                if rest.is_empty() {
+                    map.remove(name);
+                } else if let Some(v) = map.remove(name) {
+                    map.insert(name.clone(), strip_anchored(v, rest));
+                }
+                Value::Object(map)
+            }
+            other => other,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_rules_strip_error_anywhere() {
+        let rules = IgnoreRules::default_rules();
+        let value = json!({"result": {"error": "reverted", "status": "0x0"}});
+        let stripped = rules.apply("eth_getTransactionReceipt", value);
+        assert_eq!(stripped, json!({"result": {"status": "0x0"}}));
+    }
+
+    #[test]
+    fn test_method_scoped_rule_only_applies_to_that_method() {
+        let rules = IgnoreRules::from_raw(RawIgnoreRules {
+            rules: vec![IgnoreRule {
+                method: Some("eth_getTransactionReceipt".to_string()),
+                path: "error".to_string(),
+            }],
+        });
+        let value = json!({"error": "x"});
+        assert_eq!(rules.apply("eth_getTransactionReceipt", value.clone()), json!({}));
+        assert_eq!(rules.apply("eth_call", value), json!({"error": "x"}));
+    }
+
+    #[test]
+    fn test_json_path_with_wildcard() {
+        let rules = IgnoreRules::from_raw(RawIgnoreRules {
+            rules: vec![IgnoreRule { method: None, path: "result.logs[*].removed".to_string() }],
+        });
+        let value = json!({"result": {"logs": [{"removed": false, "data": "0x1"}, {"removed": true, "data": "0x2"}]}});
+        let stripped = rules.apply("eth_getLogs", value);
+        assert_eq!(
+            stripped,
+            json!({"result": {"logs": [{"data": "0x1"}, {"data": "0x2"}]}})
+        );
+    }
+}
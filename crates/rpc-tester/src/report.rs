@@ -1,84 +1,377 @@
 //! Writes a report from [`RpcTester`] run results.
 
 use super::{ReportResults, TestError};
+use crate::ignore_rules::IgnoreRules;
 use assert_json_diff::assert_json_include;
 use serde_json::Value;
 
-/// Fields that are client-specific extensions and should be ignored during comparison.
-/// For example, Nethermind includes an "error" field on reverted transaction receipts
-/// which is not part of the Ethereum JSON-RPC specification.
-const IGNORED_FIELDS: &[&str] = &["error"];
+/// JSON field names holding a 20-byte Ethereum address (40 hex digits).
+const ADDRESS_FIELDS: &[&str] = &["address", "from", "to", "contractAddress", "miner", "coinbase"];
 
-/// Prints test results to console.
+/// JSON field names holding a 32-byte hash or root (64 hex digits).
+const HASH_FIELDS: &[&str] = &[
+    "hash",
+    "blockHash",
+    "transactionHash",
+    "parentHash",
+    "stateRoot",
+    "transactionsRoot",
+    "receiptsRoot",
+    "sha3Uncles",
+    "topics",
+];
+
+/// JSON field names holding an Ethereum QUANTITY scalar: a number, safe to strip leading zeros
+/// from. Everything else is treated as an opaque DATA blob (calldata, bytecode, a bloom filter,
+/// signature bytes, ...) whose leading zero *bytes* are meaningful and must not be stripped.
+const QUANTITY_FIELDS: &[&str] = &[
+    "blockNumber",
+    "number",
+    "nonce",
+    "gas",
+    "gasLimit",
+    "gasUsed",
+    "cumulativeGasUsed",
+    "gasPrice",
+    "effectiveGasPrice",
+    "maxFeePerGas",
+    "maxPriorityFeePerGas",
+    "baseFeePerGas",
+    "value",
+    "chainId",
+    "size",
+    "timestamp",
+    "difficulty",
+    "totalDifficulty",
+    "transactionIndex",
+    "logIndex",
+    "type",
+    "v",
+    "yParity",
+];
+
+/// Controls which parts of the hex-normalization pass in [`normalize_json`] run before diffing.
+///
+/// Defaults to normalizing everything; users testing strict byte-for-byte conformance can disable
+/// individual passes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NormalizeOptions {
+    /// Lowercase all `0x`-prefixed hex strings.
+    pub lowercase_hex: bool,
+    /// Strip leading zeros from fields in [`QUANTITY_FIELDS`], collapsing an all-zero value to
+    /// `"0x0"`. Unlisted fields are DATA blobs and are left byte-for-byte as-is.
+    pub strip_leading_zeros: bool,
+    /// Left-pad known DATA fields (addresses, hashes) to their canonical length.
+    pub pad_data_fields: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self { lowercase_hex: true, strip_leading_zeros: true, pad_data_fields: true }
+    }
+}
+
+/// The outcome of comparing rpc1 against rpc2 for a single method call, independent of how it
+/// ends up being rendered (console, JSON, JUnit XML).
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum MethodOutcome {
+    Passed,
+    Diff { args: Option<String>, diff: String },
+    Rpc1Err { message: String },
+    Rpc2Err { message: String },
+}
+
+impl MethodOutcome {
+    fn is_passed(&self) -> bool {
+        matches!(self, MethodOutcome::Passed)
+    }
+}
+
+/// The result of running one rpc method against a single block/title.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct MethodReport {
+    pub name: String,
+    #[serde(flatten)]
+    pub outcome: MethodOutcome,
+}
+
+/// All method results grouped under one block title.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct BlockReport {
+    pub title: String,
+    pub methods: Vec<MethodReport>,
+}
+
+impl BlockReport {
+    fn passed(&self) -> bool {
+        self.methods.iter().all(MethodReport::passed_method)
+    }
+}
+
+impl MethodReport {
+    fn passed_method(&self) -> bool {
+        self.outcome.is_passed()
+    }
+}
+
+/// The full set of results for a test run, independent of how they're rendered.
+#[derive(Debug, Clone, serde::Serialize)]
+pub(crate) struct StructuredReport {
+    pub blocks: Vec<BlockReport>,
+}
+
+impl StructuredReport {
+    fn passed(&self) -> bool {
+        self.blocks.iter().all(BlockReport::passed)
+    }
+}
+
+/// Filters out ignored fields and normalizes hex encodings, then classifies each method result
+/// into a [`MethodOutcome`]. This is the shared pass that every output format (console, JSON,
+/// JUnit XML) renders from.
+fn build_structured_report(
+    results_by_block: ReportResults,
+    normalize: NormalizeOptions,
+    ignore_rules: &IgnoreRules,
+) -> StructuredReport {
+    let blocks = results_by_block
+        .into_iter()
+        .map(|(title, results)| {
+            let methods = results
+                .into_iter()
+                .map(|(name, result)| {
+                    let outcome = match result {
+                        Ok(_) => MethodOutcome::Passed,
+                        Err(TestError::Diff { rpc1, rpc2, args }) => {
+                            let rpc1 = normalize_json(ignore_rules.apply(&name, rpc1), None, normalize);
+                            let rpc2 = normalize_json(ignore_rules.apply(&name, rpc2), None, normalize);
+
+                            // While results are different, we only report it as error if
+                            // __RPC1__ is missing/mismatching any element against RPC2.
+                            match verify_missing_or_mismatch(rpc1, rpc2) {
+                                Some(diff) => MethodOutcome::Diff {
+                                    args: args.map(|a| a.to_string()),
+                                    diff,
+                                },
+                                None => MethodOutcome::Passed,
+                            }
+                        }
+                        Err(TestError::Rpc1Err(err)) => {
+                            MethodOutcome::Rpc1Err { message: err.to_string() }
+                        }
+                        Err(TestError::Rpc2Err(err)) => {
+                            MethodOutcome::Rpc2Err { message: err.to_string() }
+                        }
+                    };
+                    MethodReport { name, outcome }
+                })
+                .collect();
+            BlockReport { title, methods }
+        })
+        .collect();
+
+    StructuredReport { blocks }
+}
+
+/// Which shape [`report`] prints the results in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// The original human-readable console format.
+    #[default]
+    Console,
+    /// Pretty-printed JSON, for ingestion by CI dashboards.
+    Json,
+    /// JUnit XML, for CI systems that render test results natively.
+    JunitXml,
+}
+
+/// Prints test results in `format`.
 ///
 /// Returns error if RPC1 is missing/mismatching any element against RPC2 on any rpc method.
-pub(crate) fn report(results_by_block: ReportResults) -> eyre::Result<()> {
-    let mut passed = true;
+pub(crate) fn report(
+    results_by_block: ReportResults,
+    normalize: NormalizeOptions,
+    ignore_rules: &IgnoreRules,
+    format: OutputFormat,
+) -> eyre::Result<()> {
+    let structured = build_structured_report(results_by_block, normalize, ignore_rules);
+
+    match format {
+        OutputFormat::Console => print_console_report(&structured),
+        OutputFormat::Json => println!("{}", report_json(&structured)?),
+        OutputFormat::JunitXml => println!("{}", report_junit_xml(&structured)),
+    }
+
+    if structured.passed() {
+        Ok(())
+    } else {
+        Err(eyre::eyre!("Failed."))
+    }
+}
+
+/// Renders a [`StructuredReport`] in the original human-readable console format.
+fn print_console_report(structured: &StructuredReport) {
     println!("\n--- RPC Method Test Results ---");
     println!("  (expected = rpc2, actual = rpc1)\n");
 
-    for (title, results) in results_by_block {
-        let mut passed_title = true;
-
-        for (name, result) in results {
-            match result {
-                Ok(_) => {}
-                Err(TestError::Diff { rpc1, rpc2, args }) => {
-                    // Filter out client-specific extension fields before comparison
-                    let rpc1 = filter_ignored_fields(rpc1);
-                    let rpc2 = filter_ignored_fields(rpc2);
-
-                    // While results are different, we only report it as error if __RPC1__ is
-                    // missing/mismatching any element against RPC2.
-                    if let Some(diffs) = verify_missing_or_mismatch(rpc1, rpc2) {
-                        if passed_title {
-                            passed_title = false;
-                            println!("\n{title} ❌");
-                        }
-                        println!("    {name}: ❌ Failure");
-                        if let Some(args) = args {
-                            println!("      args: {args}");
-                        }
-                        println!("{diffs}");
+    for block in &structured.blocks {
+        if block.passed() {
+            println!("{} ✅", block.title);
+            continue;
+        }
+
+        println!("\n{} ❌", block.title);
+        for method in &block.methods {
+            match &method.outcome {
+                MethodOutcome::Passed => {}
+                MethodOutcome::Diff { args, diff } => {
+                    println!("    {}: ❌ Failure", method.name);
+                    if let Some(args) = args {
+                        println!("      args: {args}");
                     }
+                    println!("{diff}");
                 }
-                Err(TestError::Rpc1Err(err) | TestError::Rpc2Err(err)) => {
-                    passed_title = false;
-                    println!("\n{title} ❌");
-                    println!("    {name}: ❌ {err}");
+                MethodOutcome::Rpc1Err { message } | MethodOutcome::Rpc2Err { message } => {
+                    println!("    {}: ❌ {message}", method.name);
                 }
             }
         }
-
-        if passed_title {
-            println!("{title} ✅");
-        }
-        passed &= passed_title;
     }
 
     println!("--------------------------------\n");
-    if passed {
-        Ok(())
-    } else {
-        Err(eyre::eyre!("Failed."))
+}
+
+/// Serializes a [`StructuredReport`] as JSON, for ingestion by CI dashboards.
+pub(crate) fn report_json(structured: &StructuredReport) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(structured)
+}
+
+/// Serializes a [`StructuredReport`] as JUnit XML (one `<testsuite>` per block title, one
+/// `<testcase>` per method, failures carrying the diff or rpc error as the failure message).
+pub(crate) fn report_junit_xml(structured: &StructuredReport) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+
+    for block in &structured.blocks {
+        let failures = block.methods.iter().filter(|m| !m.outcome.is_passed()).count();
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            xml_escape(&block.title),
+            block.methods.len(),
+            failures,
+        ));
+
+        for method in &block.methods {
+            out.push_str(&format!("    <testcase name=\"{}\">\n", xml_escape(&method.name)));
+            match &method.outcome {
+                MethodOutcome::Passed => {}
+                MethodOutcome::Diff { diff, .. } => {
+                    out.push_str(&format!(
+                        "      <failure message=\"diff\">{}</failure>\n",
+                        xml_escape(diff)
+                    ));
+                }
+                MethodOutcome::Rpc1Err { message } | MethodOutcome::Rpc2Err { message } => {
+                    out.push_str(&format!(
+                        "      <failure message=\"rpc error\">{}</failure>\n",
+                        xml_escape(message)
+                    ));
+                }
+            }
+            out.push_str("    </testcase>\n");
+        }
+
+        out.push_str("  </testsuite>\n");
     }
+
+    out.push_str("</testsuites>\n");
+    out
 }
 
-/// Recursively removes fields from JSON values that are in the [`IGNORED_FIELDS`] list.
-/// This is used to filter out client-specific extensions before comparison.
-fn filter_ignored_fields(value: Value) -> Value {
+/// Escapes the handful of characters that are unsafe inside XML text/attribute content.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Recursively canonicalizes `0x`-prefixed hex strings so that two spec-compliant clients that
+/// merely encode the same value differently (`"0x1"` vs `"0x01"`, mixed-case hex, a left-padded
+/// address, or one client returning a QUANTITY as a JSON number instead of hex) don't show up as a
+/// diff. `key` is the JSON field name `value` was found under, used to look up a canonical length
+/// for DATA fields and to tell whether a bare number is a QUANTITY; pass `None` at the root.
+fn normalize_json(value: Value, key: Option<&str>, opts: NormalizeOptions) -> Value {
     match value {
-        Value::Object(mut map) => {
-            for field in IGNORED_FIELDS {
-                map.remove(*field);
-            }
-            Value::Object(map.into_iter().map(|(k, v)| (k, filter_ignored_fields(v))).collect())
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let normalized = normalize_json(v, Some(k.as_str()), opts);
+                    (k, normalized)
+                })
+                .collect(),
+        ),
+        Value::Array(arr) => {
+            Value::Array(arr.into_iter().map(|v| normalize_json(v, key, opts)).collect())
         }
-        Value::Array(arr) => Value::Array(arr.into_iter().map(filter_ignored_fields).collect()),
+        Value::String(s) => Value::String(normalize_hex_string(&s, key, opts)),
+        Value::Number(n) => match quantity_number_to_hex(&n, key, opts) {
+            Some(hex) => Value::String(hex),
+            None => Value::Number(n),
+        },
         other => other,
     }
 }
 
+/// Converts a decimal QUANTITY field (e.g. `"gas": 10`) to the same canonical hex form
+/// [`normalize_hex_string`] would produce for `"gas": "0xa"`, so the two don't show up as a diff.
+/// Returns `None` for non-QUANTITY fields or numbers that don't fit a `u64` (QUANTITY values are
+/// never negative or fractional).
+fn quantity_number_to_hex(n: &serde_json::Number, key: Option<&str>, opts: NormalizeOptions) -> Option<String> {
+    if !opts.strip_leading_zeros || !key.is_some_and(|k| QUANTITY_FIELDS.contains(&k)) {
+        return None;
+    }
+    n.as_u64().map(|v| format!("0x{v:x}"))
+}
+
+/// Canonicalizes a single hex string. Leaves non-hex strings untouched.
+fn normalize_hex_string(s: &str, key: Option<&str>, opts: NormalizeOptions) -> String {
+    let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) else {
+        return s.to_string();
+    };
+    if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return s.to_string();
+    }
+
+    let mut hex = if opts.lowercase_hex { hex.to_ascii_lowercase() } else { hex.to_string() };
+
+    // Only QUANTITY fields encode a number, where leading zeros are pure formatting. Everything
+    // else is an opaque DATA blob (calldata, bytecode, a bloom filter, signature bytes, ...) whose
+    // leading zero bytes are part of the value and must be left alone.
+    if opts.strip_leading_zeros && key.is_some_and(|k| QUANTITY_FIELDS.contains(&k)) {
+        let trimmed = hex.trim_start_matches('0');
+        hex = if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() };
+    }
+
+    if opts.pad_data_fields {
+        if let Some(len) = key.and_then(canonical_hex_len) {
+            if hex.len() < len {
+                hex = format!("{hex:0>len$}");
+            }
+        }
+    }
+
+    format!("0x{hex}")
+}
+
+/// Returns the canonical hex-digit length (excluding `0x`) for a well-known DATA field name.
+fn canonical_hex_len(key: &str) -> Option<usize> {
+    if ADDRESS_FIELDS.contains(&key) {
+        Some(40)
+    } else if HASH_FIELDS.contains(&key) {
+        Some(64)
+    } else {
+        None
+    }
+}
+
 /// Verifies if there is any missing field/element from rpc1 comparing it to rpc2.
 fn verify_missing_or_mismatch(rpc1: Value, rpc2: Value) -> Option<String> {
     let default_panic_hook = std::panic::take_hook();
@@ -104,3 +397,159 @@ fn verify_missing_or_mismatch(rpc1: Value, rpc2: Value) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_json_converts_decimal_quantity_to_hex() {
+        // One client returning `"gas": 10` and the other `"gas": "0xa"` must normalize to the
+        // same value so it doesn't show up as a spurious diff.
+        let opts = NormalizeOptions::default();
+        let decimal = json!({"gas": 10});
+        let hex = json!({"gas": "0xa"});
+        assert_eq!(normalize_json(decimal, None, opts), normalize_json(hex, None, opts));
+    }
+
+    #[test]
+    fn test_normalize_json_leaves_unlisted_numbers_untouched() {
+        // Only QUANTITY fields get converted; an unrelated number (or a DATA-shaped field) is left
+        // as a JSON number rather than being coerced into hex.
+        let opts = NormalizeOptions::default();
+        assert_eq!(normalize_json(json!({"someCount": 10}), None, opts), json!({"someCount": 10}));
+    }
+
+    #[test]
+    fn test_quantity_number_to_hex() {
+        let opts = NormalizeOptions::default();
+        assert_eq!(quantity_number_to_hex(&10.into(), Some("gas"), opts), Some("0xa".to_string()));
+        assert_eq!(quantity_number_to_hex(&10.into(), Some("someCount"), opts), None);
+        assert_eq!(quantity_number_to_hex(&0.into(), Some("gas"), opts), Some("0x0".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_hex_string_strips_leading_zeros_on_quantity_fields() {
+        let opts = NormalizeOptions::default();
+        assert_eq!(normalize_hex_string("0x0a", Some("gas"), opts), "0xa");
+        assert_eq!(normalize_hex_string("0x0000", Some("blockNumber"), opts), "0x0");
+    }
+
+    #[test]
+    fn test_normalize_hex_string_leaves_unlisted_data_fields_untouched() {
+        let opts = NormalizeOptions::default();
+        // Calldata, bytecode and other unlisted DATA blobs must not have leading zero bytes
+        // stripped: that changes the value, not just its formatting.
+        assert_eq!(
+            normalize_hex_string("0x00a9059cbb", Some("input"), opts),
+            "0x00a9059cbb"
+        );
+        assert_eq!(normalize_hex_string("0x00a9", Some("data"), opts), "0x00a9");
+        assert_eq!(normalize_hex_string("0x00a9", Some("code"), opts), "0x00a9");
+        assert_eq!(normalize_hex_string("0x00a9", Some("logsBloom"), opts), "0x00a9");
+        assert_eq!(normalize_hex_string("0x00a9", Some("extraData"), opts), "0x00a9");
+        assert_eq!(normalize_hex_string("0x00a9", Some("r"), opts), "0x00a9");
+        assert_eq!(normalize_hex_string("0x00a9", Some("s"), opts), "0x00a9");
+    }
+
+    #[test]
+    fn test_normalize_hex_string_leaves_unkeyed_hex_untouched() {
+        // No field name (e.g. a raw array element) means we can't tell QUANTITY from DATA, so
+        // stripping must not apply.
+        let opts = NormalizeOptions::default();
+        assert_eq!(normalize_hex_string("0x00a9", None, opts), "0x00a9");
+    }
+
+    #[test]
+    fn test_normalize_hex_string_pads_known_data_fields() {
+        let opts = NormalizeOptions::default();
+        assert_eq!(
+            normalize_hex_string("0x1", Some("address"), opts),
+            format!("0x{}", "0".repeat(39) + "1")
+        );
+        assert_eq!(
+            normalize_hex_string("0x1", Some("blockHash"), opts),
+            format!("0x{}", "0".repeat(63) + "1")
+        );
+    }
+
+    #[test]
+    fn test_normalize_hex_string_lowercases() {
+        let opts = NormalizeOptions::default();
+        assert_eq!(normalize_hex_string("0xABCDEF", Some("gas"), opts), "0xabcdef");
+    }
+
+    #[test]
+    fn test_normalize_hex_string_ignores_non_hex() {
+        let opts = NormalizeOptions::default();
+        assert_eq!(normalize_hex_string("hello", Some("gas"), opts), "hello");
+        assert_eq!(normalize_hex_string("0x", Some("gas"), opts), "0x");
+    }
+
+    #[test]
+    fn test_canonical_hex_len() {
+        assert_eq!(canonical_hex_len("address"), Some(40));
+        assert_eq!(canonical_hex_len("blockHash"), Some(64));
+        assert_eq!(canonical_hex_len("gas"), None);
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("<a> & \"quoted\""),
+            "&lt;a&gt; &amp; &quot;quoted&quot;"
+        );
+    }
+
+    fn sample_report() -> StructuredReport {
+        StructuredReport {
+            blocks: vec![
+                BlockReport {
+                    title: "block 1".to_string(),
+                    methods: vec![MethodReport {
+                        name: "eth_getLogs".to_string(),
+                        outcome: MethodOutcome::Passed,
+                    }],
+                },
+                BlockReport {
+                    title: "block 2".to_string(),
+                    methods: vec![
+                        MethodReport {
+                            name: "eth_call".to_string(),
+                            outcome: MethodOutcome::Diff {
+                                args: Some("[\"0x1\"]".to_string()),
+                                diff: "mismatch".to_string(),
+                            },
+                        },
+                        MethodReport {
+                            name: "eth_blockNumber".to_string(),
+                            outcome: MethodOutcome::Rpc1Err { message: "timed out".to_string() },
+                        },
+                    ],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_report_json_round_trips_outcome() {
+        let json = report_json(&sample_report()).expect("serializes");
+        let value: Value = serde_json::from_str(&json).expect("valid json");
+        assert_eq!(value["blocks"][0]["title"], "block 1");
+        assert_eq!(value["blocks"][0]["methods"][0]["status"], "passed");
+        assert_eq!(value["blocks"][1]["methods"][0]["status"], "diff");
+        assert_eq!(value["blocks"][1]["methods"][0]["diff"], "mismatch");
+        assert_eq!(value["blocks"][1]["methods"][1]["status"], "rpc1_err");
+    }
+
+    #[test]
+    fn test_report_junit_xml_reports_tests_and_failures() {
+        let xml = report_junit_xml(&sample_report());
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+        assert!(xml.contains("<testsuite name=\"block 1\" tests=\"1\" failures=\"0\">"));
+        assert!(xml.contains("<testsuite name=\"block 2\" tests=\"2\" failures=\"2\">"));
+        assert!(xml.contains("<failure message=\"diff\">mismatch</failure>"));
+        assert!(xml.contains("<failure message=\"rpc error\">timed out</failure>"));
+    }
+}